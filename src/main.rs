@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     io::Write,
     time::Duration,
 };
@@ -12,6 +12,9 @@ struct Channel {
     url: String,
     author: String,
     group: String,
+    // Additional groups assigned by `ChannelPatterns`, on top of the OPML
+    // group nesting.
+    extra_groups: BTreeSet<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -21,49 +24,222 @@ struct FeedsItem {
     date: chrono::DateTime<chrono::FixedOffset>,
     url: String,
     group: String,
+    // Stable identity used to detect items already seen in a prior run.
+    // This is feed_rs's own normalized `entry.id`: the underlying GUID for
+    // RSS, the mandatory id for Atom/JSON Feed, or the entry's link when
+    // the feed supplies none of those — feed_rs derives it the same way
+    // on every parse of the same entry, so it stays stable across
+    // re-fetches.
+    id: String,
+    extra_groups: BTreeSet<String>,
+    // Podcast audio attachment, present when the entry carries an
+    // `<enclosure>` (or equivalent media content).
+    enclosure: Option<Enclosure>,
 }
 
-fn parser_rss(feed: rss::Channel, channel: &Channel) -> Vec<FeedsItem> {
-    let mut feeds = Vec::new();
-    for item in feed.items {
-        let title = item.title.unwrap_or("".to_string());
-        let date = item.pub_date.expect("error format!");
-        let date = match diligent_date_parser::parse_date(date.as_str()) {
-            Some(date) => date,
-            None => {
-                println!(
-                    "error on parsing date `{}`, at parsering {}",
-                    date.as_str(),
-                    channel.url
-                );
+#[derive(Debug, Clone)]
+struct Enclosure {
+    url: String,
+    mime_type: String,
+    length: Option<u64>,
+    duration: Option<Duration>,
+}
+
+// Regex-based routing rules that assign a channel to extra groups based on
+// its URL or title, independent of OPML nesting. Configured as
+// comma-separated `regex:group1 group2` rules.
+#[derive(Debug, Default)]
+struct ChannelPatterns(Vec<(regex::Regex, Vec<String>)>);
+
+impl ChannelPatterns {
+    fn parse(spec: &str) -> Self {
+        let mut rules = Vec::new();
+        for rule in spec.split(',') {
+            let rule = rule.trim();
+            if rule.is_empty() {
                 continue;
             }
-        };
-        feeds.push(FeedsItem {
-            title,
-            author: channel.author.to_string(),
-            date,
-            url: item.link.unwrap(),
-            group: channel.group.to_string(),
-        })
+            let Some((pattern, groups)) = rule.split_once(':') else {
+                println!("invalid channel pattern rule `{}`, expected `regex:group1 group2`", rule);
+                continue;
+            };
+            let regex = match regex::Regex::new(&std::format!("^(?:{})$", pattern)) {
+                Ok(regex) => regex,
+                Err(err) => {
+                    println!("invalid channel pattern regex `{}`: {}", pattern, err);
+                    continue;
+                }
+            };
+            let groups = groups
+                .split_whitespace()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            rules.push((regex, groups));
+        }
+        ChannelPatterns(rules)
+    }
+
+    // Every group whose anchored regex matches the channel's URL or title.
+    fn find_groups(&self, url: &str, title: &str) -> BTreeSet<String> {
+        let mut groups = BTreeSet::new();
+        for (regex, names) in &self.0 {
+            if regex.is_match(url) || regex.is_match(title) {
+                groups.extend(names.iter().cloned());
+            }
+        }
+        groups
     }
-    return feeds;
 }
 
-fn parser_atom(feed: atom_syndication::Feed, channel: &Channel) -> Vec<FeedsItem> {
-    let mut feeds = Vec::new();
-    for item in feed.entries() {
-        let title = item.title().to_string();
-        let date = item.published().unwrap_or(item.updated());
-        feeds.push(FeedsItem {
-            title,
-            author: channel.author.to_string(),
-            date: date.clone(),
-            url: item.links[0].href.clone(),
-            group: channel.group.to_string(),
+// Looks up a raw, unparsed value stashed by feed_rs in a feed's namespaced
+// extension elements (e.g. `itunes:duration`, or a `pubDate` feed_rs
+// couldn't normalize itself).
+fn extension_value(entry: &feed_rs::model::Entry, ns: &str, key: &str) -> Option<String> {
+    entry
+        .extensions
+        .get(ns)?
+        .get(key)?
+        .first()?
+        .value
+        .clone()
+}
+
+fn extension_date(entry: &feed_rs::model::Entry) -> Option<String> {
+    for ns in ["rss", "dc", ""] {
+        for key in ["pubDate", "date"] {
+            if let Some(value) = extension_value(entry, ns, key) {
+                return Some(value);
+            }
+        }
+    }
+    None
+}
+
+// Parses `HH:MM:SS`, `MM:SS`, or a bare seconds count (the shapes
+// `itunes:duration` shows up in across podcast feeds) into a `Duration`.
+fn parse_itunes_duration(input: &str) -> Option<Duration> {
+    let mut seconds: u64 = 0;
+    let mut parts = 0;
+    for part in input.trim().split(':') {
+        seconds = seconds.checked_mul(60)?.checked_add(part.parse::<u64>().ok()?)?;
+        parts += 1;
+    }
+    if parts == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+// Illegal path characters (and overly long names) sanitized out of an
+// episode title so a downstream download step can write audio to a
+// deterministic, filesystem-safe local filename.
+fn sanitize_filename(input: &str) -> String {
+    const MAX_LEN: usize = 100;
+    let mut name: String = input
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
         })
+        .collect();
+    name = name.trim_matches('_').to_string();
+    name.truncate(MAX_LEN);
+    if name.is_empty() {
+        "episode".to_string()
+    } else {
+        name
     }
-    return feeds;
+}
+
+// A safe local filename for an episode's enclosure, reusing the enclosure
+// URL's extension when it looks like one. The item's date is prefixed so
+// that two episodes sharing a title (e.g. "Weekly Update") don't collide
+// on the same file.
+fn episode_filename(item: &FeedsItem) -> Option<String> {
+    let enclosure = item.enclosure.as_ref()?;
+    let path = enclosure
+        .url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(&enclosure.url);
+    let ext = path
+        .rsplit('.')
+        .next()
+        .filter(|ext| ext.len() <= 5 && !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("mp3");
+    Some(std::format!(
+        "{}-{}.{}",
+        item.date.format("%Y-%m-%d"),
+        sanitize_filename(&item.title),
+        ext
+    ))
+}
+
+fn entry_enclosure(entry: &feed_rs::model::Entry) -> Option<Enclosure> {
+    let media = entry.media.first()?;
+    let content = media.content.first()?;
+    let url = content.url.as_ref()?.to_string();
+    let mime_type = content
+        .content_type
+        .as_ref()
+        .map(|mime| mime.to_string())
+        .unwrap_or_default();
+    let length = content.size;
+    let duration = media
+        .duration
+        .or_else(|| extension_value(entry, "itunes", "duration").and_then(|raw| parse_itunes_duration(&raw)));
+
+    Some(Enclosure {
+        url,
+        mime_type,
+        length,
+        duration,
+    })
+}
+
+fn entry_to_feeds_item(entry: feed_rs::model::Entry, channel: &Channel) -> Option<FeedsItem> {
+    let Some(link) = entry.links.first() else {
+        println!("skipping entry with no link, at parsering {}", channel.url);
+        return None;
+    };
+    let url = link.href.clone();
+    let title = entry.title.clone().map(|t| t.content).unwrap_or_default();
+    let author = entry
+        .authors
+        .first()
+        .map(|person| person.name.clone())
+        .unwrap_or_else(|| channel.author.to_string());
+    let enclosure = entry_enclosure(&entry);
+
+    let date = match entry.published.or(entry.updated) {
+        Some(date) => date.fixed_offset(),
+        None => match extension_date(&entry).and_then(|raw| diligent_date_parser::parse_date(&raw))
+        {
+            Some(date) => date,
+            None => {
+                println!(
+                    "error on parsing date for `{}`, at parsering {}",
+                    title, channel.url
+                );
+                return None;
+            }
+        },
+    };
+
+    Some(FeedsItem {
+        title,
+        author,
+        date,
+        url,
+        group: channel.group.to_string(),
+        id: entry.id,
+        extra_groups: channel.extra_groups.clone(),
+        enclosure,
+    })
 }
 
 async fn fetch_feed(channels: &Vec<Channel>) -> Vec<FeedsItem> {
@@ -107,24 +283,23 @@ async fn fetch_feed(channels: &Vec<Channel>) -> Vec<FeedsItem> {
             }
         };
         let read_buf = &content[..];
-        match rss::Channel::read_from(read_buf) {
-            Ok(content) => {
-                feeds.append(&mut parser_rss(content, channel));
-            }
-            Err(_) => match atom_syndication::Feed::read_from(read_buf) {
-                Ok(content) => {
-                    feeds.append(&mut parser_atom(content, channel));
-                }
-                Err(_) => {
-                    println!("parse error at {}", channel.url);
+        match feed_rs::parser::parse(read_buf) {
+            Ok(parsed) => {
+                for entry in parsed.entries {
+                    if let Some(item) = entry_to_feeds_item(entry, channel) {
+                        feeds.push(item);
+                    }
                 }
-            },
+            }
+            Err(_) => {
+                println!("parse error at {}", channel.url);
+            }
         }
     }
     return feeds;
 }
 
-fn get_channels(opml_file: opml::OPML) -> Vec<Channel> {
+fn get_channels(opml_file: opml::OPML, patterns: &ChannelPatterns) -> Vec<Channel> {
     let mut channels = Vec::new();
 
     for item in opml_file.body.outlines {
@@ -133,10 +308,14 @@ fn get_channels(opml_file: opml::OPML) -> Vec<Channel> {
                 if outline_type != "rss" {
                     panic!("type in group should be `rss`.")
                 }
+                let url = item.xml_url.unwrap();
+                let author = item.title.unwrap();
+                let extra_groups = patterns.find_groups(&url, &author);
                 channels.push(Channel {
-                    url: item.xml_url.unwrap(),
-                    author: item.title.unwrap(),
+                    url,
+                    author,
                     group: "".to_string(),
+                    extra_groups,
                 });
             }
             None => {
@@ -147,10 +326,14 @@ fn get_channels(opml_file: opml::OPML) -> Vec<Channel> {
                     if item.r#type.as_ref().unwrap() != "rss" {
                         panic!("type in group should be `rss`.")
                     }
+                    let url = item.xml_url.unwrap();
+                    let author = item.title.unwrap();
+                    let extra_groups = patterns.find_groups(&url, &author);
                     channels.push(Channel {
-                        url: item.xml_url.unwrap(),
-                        author: item.title.unwrap(),
+                        url,
+                        author,
                         group: group_name.to_string(),
+                        extra_groups,
                     });
                 }
             }
@@ -166,12 +349,14 @@ fn split_by_group(feeds: &Vec<FeedsItem>) -> HashMap<String, Vec<FeedsItem>> {
     let past_year = now.with_year(now.year() - 1).unwrap();
 
     for feed in feeds {
-        let mut keys = vec!["".to_string()];
+        let mut keys = BTreeSet::new();
+        keys.insert("".to_string());
         if !feed.group.is_empty() {
-            keys.push(feed.group.clone());
+            keys.insert(feed.group.clone());
         }
+        keys.extend(feed.extra_groups.iter().cloned());
         if feed.date.signed_duration_since(past_year).num_seconds() >= 0 {
-            keys.push("this-year".to_string());
+            keys.insert("this-year".to_string());
         }
 
         for k in keys {
@@ -218,11 +403,304 @@ fn generate_md(list: &Vec<FeedsItem>) -> String {
     return buf.join("\n\n");
 }
 
+// Renders year-headed episode entries, same as `generate_md`. Relies on
+// `list` already being sorted by date (as `main` sorts `feeds` before
+// splitting into groups) to emit each year heading exactly once.
+fn generate_episode_md(list: &Vec<FeedsItem>) -> String {
+    let episodes: Vec<&FeedsItem> = list.iter().filter(|item| item.enclosure.is_some()).collect();
+    if episodes.is_empty() {
+        return "".to_string();
+    }
+
+    let fmt_year = |item: &FeedsItem| std::format!("# {}", item.date.year());
+
+    let fmt_item = |item: &FeedsItem| {
+        let enclosure = item.enclosure.as_ref().unwrap();
+        let duration = enclosure
+            .duration
+            .map(|d| std::format!("{}s", d.as_secs()))
+            .unwrap_or_else(|| "unknown".to_string());
+        let filename = episode_filename(item).unwrap_or_else(|| "episode".to_string());
+        std::format!(
+            "{}, @{}, [{}]({}) ({}, {}, `{}`)",
+            item.date.format("%Y-%m-%d"),
+            item.author,
+            item.title,
+            enclosure.url,
+            enclosure.mime_type,
+            duration,
+            filename
+        )
+    };
+
+    let mut buf = Vec::new();
+    buf.push(fmt_year(episodes[0]));
+
+    for i in 1..episodes.len() {
+        let item = episodes[i];
+        if item.date.year() != episodes[i - 1].date.year() {
+            buf.push(fmt_year(item));
+        }
+        buf.push(fmt_item(item));
+    }
+    return buf.join("\n\n");
+}
+
+// Default template used for a feed item's rendered title, and the fallback
+// applied whenever a user-supplied template is malformed.
+const DEFAULT_ITEM_TITLE_TEMPLATE: &str = "{title}";
+
+// A template is well-formed if every `{...}` placeholder is one of the
+// known names and braces are balanced and non-nested.
+fn is_valid_item_title_template(template: &str) -> bool {
+    let mut depth = 0i32;
+    let mut name = String::new();
+    for c in template.chars() {
+        match c {
+            '{' => {
+                depth += 1;
+                if depth > 1 {
+                    return false;
+                }
+                name.clear();
+            }
+            '}' => {
+                depth -= 1;
+                if depth < 0 {
+                    return false;
+                }
+                if !matches!(name.as_str(), "title" | "author" | "group") {
+                    return false;
+                }
+            }
+            _ if depth == 1 => name.push(c),
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+// Substitutes placeholders in a single pass over the original template, so
+// text substituted for one placeholder (e.g. a title that itself contains
+// the literal string `{author}`) is never re-scanned and corrupted by a
+// later substitution.
+fn render_item_title(template: &str, item: &FeedsItem) -> String {
+    let template = if is_valid_item_title_template(template) {
+        template
+    } else {
+        DEFAULT_ITEM_TITLE_TEMPLATE
+    };
+
+    let mut out = String::with_capacity(template.len());
+    let mut name = String::new();
+    let mut in_placeholder = false;
+    for c in template.chars() {
+        match c {
+            '{' => {
+                in_placeholder = true;
+                name.clear();
+            }
+            '}' => {
+                in_placeholder = false;
+                match name.as_str() {
+                    "title" => out.push_str(&item.title),
+                    "author" => out.push_str(&item.author),
+                    "group" => out.push_str(&item.group),
+                    _ => {}
+                }
+            }
+            _ if in_placeholder => name.push(c),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn generate_rss(
+    list: &Vec<FeedsItem>,
+    title: &str,
+    link: &str,
+    description: &str,
+    item_title_template: &str,
+) -> rss::Channel {
+    let items: Vec<rss::Item> = list
+        .iter()
+        .map(|item| {
+            rss::ItemBuilder::default()
+                .title(Some(render_item_title(item_title_template, item)))
+                .link(Some(item.url.clone()))
+                .pub_date(Some(item.date.to_rfc2822()))
+                .author(Some(item.author.clone()))
+                .build()
+        })
+        .collect();
+
+    rss::ChannelBuilder::default()
+        .title(title)
+        .link(link)
+        .description(description)
+        .items(items)
+        .build()
+}
+
+fn generate_atom(
+    list: &Vec<FeedsItem>,
+    title: &str,
+    link: &str,
+    item_title_template: &str,
+) -> atom_syndication::Feed {
+    let entries: Vec<atom_syndication::Entry> = list
+        .iter()
+        .map(|item| {
+            atom_syndication::EntryBuilder::default()
+                .title(render_item_title(item_title_template, item))
+                .id(item.url.clone())
+                .links(vec![atom_syndication::Link {
+                    href: item.url.clone(),
+                    ..Default::default()
+                }])
+                .published(Some(item.date))
+                .authors(vec![atom_syndication::Person {
+                    name: item.author.clone(),
+                    ..Default::default()
+                }])
+                .build()
+        })
+        .collect();
+
+    atom_syndication::FeedBuilder::default()
+        .title(title)
+        .id(link)
+        .entries(entries)
+        .build()
+}
+
+// Resolves the id-set cache path: an explicit CLI path wins, otherwise it
+// follows the XDG base-directory convention ($XDG_CACHE_HOME, then
+// $HOME/.cache).
+fn resolve_cache_path(cli_path: Option<&str>) -> std::path::PathBuf {
+    if let Some(path) = cli_path {
+        return std::path::PathBuf::from(path);
+    }
+    let base = std::env::var("XDG_CACHE_HOME")
+        .map(std::path::PathBuf::from)
+        .or_else(|_| std::env::var("HOME").map(|home| std::path::PathBuf::from(home).join(".cache")))
+        .expect("could not resolve a cache directory: set XDG_CACHE_HOME or HOME, or pass a cache path");
+    base.join("github-action-rss").join("seen.json")
+}
+
+// Items from this run whose id wasn't in the prior run's seen-id set.
+fn diff_new_items(feeds: &Vec<FeedsItem>, seen_ids: &HashSet<String>) -> Vec<FeedsItem> {
+    feeds
+        .iter()
+        .filter(|f| !seen_ids.contains(&f.id))
+        .cloned()
+        .collect()
+}
+
+// The id-set to persist for next run: only ids still present in this run's
+// feeds, so ids belonging to items that have since scrolled out of their
+// feed are evicted instead of accumulating in the cache file forever.
+fn current_ids(feeds: &Vec<FeedsItem>) -> HashSet<String> {
+    feeds.iter().map(|f| f.id.clone()).collect()
+}
+
+fn load_seen_ids(path: &std::path::Path) -> HashSet<String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_default(),
+        Err(_) => HashSet::new(),
+    }
+}
+
+fn save_seen_ids_atomic(path: &std::path::Path, ids: &HashSet<String>) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut tmp_path = path.to_path_buf();
+    let tmp_name = std::format!(
+        "{}.tmp",
+        path.file_name().unwrap().to_string_lossy()
+    );
+    tmp_path.set_file_name(tmp_name);
+
+    let serialized = serde_json::to_string(ids).expect("id set should always serialize");
+    std::fs::write(&tmp_path, serialized)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// Writes `contents` to `path` via a temp file in the same directory,
+// persisted (renamed) over the final path, so a crash mid-write never
+// leaves a truncated file and a failed write leaves the previous output
+// intact.
+fn write_atomic(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty());
+    let mut tmp = match dir {
+        Some(dir) => tempfile::NamedTempFile::new_in(dir)?,
+        None => tempfile::NamedTempFile::new()?,
+    };
+    tmp.write_all(contents)?;
+    tmp.persist(path)
+        .map_err(|persist_err| persist_err.error)?;
+    Ok(())
+}
+
+fn write_group_outputs(
+    out_dir: &std::path::Path,
+    group: &str,
+    items: &Vec<FeedsItem>,
+    new_items: Option<&Vec<FeedsItem>>,
+    feed_title: &str,
+    feed_link: &str,
+    feed_description: &str,
+    item_title_template: &str,
+) -> std::io::Result<()> {
+    let mut md_file_path = out_dir.to_path_buf();
+    md_file_path.push(std::format!("{}.md", group));
+    write_atomic(&md_file_path, generate_md(items).as_bytes())?;
+
+    let mut episodes_md_path = out_dir.to_path_buf();
+    episodes_md_path.push(std::format!("{}.episodes.md", group));
+    write_atomic(&episodes_md_path, generate_episode_md(items).as_bytes())?;
+
+    let rss_channel = generate_rss(
+        items,
+        feed_title,
+        feed_link,
+        feed_description,
+        item_title_template,
+    );
+    let mut rss_buf = Vec::new();
+    rss_channel
+        .write_to(&mut rss_buf)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    let mut rss_file_path = out_dir.to_path_buf();
+    rss_file_path.push(std::format!("{}.xml", group));
+    write_atomic(&rss_file_path, &rss_buf)?;
+
+    let atom_feed = generate_atom(items, feed_title, feed_link, item_title_template);
+    let mut atom_buf = Vec::new();
+    atom_feed
+        .write_to(&mut atom_buf)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+    let mut atom_file_path = out_dir.to_path_buf();
+    atom_file_path.push(std::format!("{}.atom.xml", group));
+    write_atomic(&atom_file_path, &atom_buf)?;
+
+    if let Some(new_items) = new_items {
+        let mut new_md_path = out_dir.to_path_buf();
+        new_md_path.push(std::format!("{}.new.md", group));
+        write_atomic(&new_md_path, generate_md(new_items).as_bytes())?;
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 3 {
-        println!("useage: ga-rss <opml> <md-dir>");
+        println!("useage: ga-rss <opml> <out-dir> [feed-title] [feed-link] [feed-description] [item-title-template] [cache-path] [channel-patterns]");
         return;
     }
     let opml_path = std::path::PathBuf::from(&args[1]);
@@ -231,12 +709,34 @@ async fn main() {
     let mut file = std::fs::File::open(opml_path).unwrap();
     let opml_file = opml::OPML::from_reader(&mut file).unwrap();
 
-    let channels = get_channels(opml_file);
+    let opml_title = opml_file.head.as_ref().and_then(|h| h.title.clone());
+
+    let feed_title = args
+        .get(3)
+        .cloned()
+        .or(opml_title)
+        .unwrap_or("RSS Feed".to_string());
+    let feed_link = args.get(4).cloned().unwrap_or("".to_string());
+    let feed_description = args.get(5).cloned().unwrap_or("".to_string());
+    let item_title_template = args
+        .get(6)
+        .cloned()
+        .unwrap_or(DEFAULT_ITEM_TITLE_TEMPLATE.to_string());
+    let cache_path = resolve_cache_path(args.get(7).map(|s| s.as_str()));
+    let channel_patterns = ChannelPatterns::parse(args.get(8).map(|s| s.as_str()).unwrap_or(""));
+
+    let channels = get_channels(opml_file, &channel_patterns);
     let mut feeds = fetch_feed(&channels).await;
     feeds.sort_by_key(|f| f.date);
     feeds.reverse();
 
+    let seen_ids = load_seen_ids(&cache_path);
+    let new_feeds = diff_new_items(&feeds, &seen_ids);
+    let updated_ids = current_ids(&feeds);
+    save_seen_ids_atomic(&cache_path, &updated_ids).unwrap();
+
     let s = split_by_group(&feeds);
+    let new_by_group = split_by_group(&new_feeds);
     // println!("s = {:#?}", s);
 
     for (k, v) in &s {
@@ -245,10 +745,464 @@ async fn main() {
         } else {
             k.to_string()
         };
-        let mut path = md_path.clone();
-        path.push(std::format!("{}.md", group));
-        let mut output = std::fs::File::create(path).unwrap();
-        let doc = generate_md(&v);
-        output.write(doc.as_bytes()).unwrap();
+
+        if let Err(err) = write_group_outputs(
+            &md_path,
+            &group,
+            v,
+            new_by_group.get(k),
+            &feed_title,
+            &feed_link,
+            &feed_description,
+            &item_title_template,
+        ) {
+            println!("error writing outputs for group `{}`: {}", group, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item_with(title: &str, author: &str, group: &str) -> FeedsItem {
+        FeedsItem {
+            title: title.to_string(),
+            author: author.to_string(),
+            date: chrono::DateTime::parse_from_rfc2822("Mon, 1 Jan 2024 00:00:00 +0000").unwrap(),
+            url: "https://example.com".to_string(),
+            group: group.to_string(),
+            id: "id".to_string(),
+            extra_groups: BTreeSet::new(),
+            enclosure: None,
+        }
+    }
+
+    fn test_channel(author: &str) -> Channel {
+        Channel {
+            url: "https://example.com/feed".to_string(),
+            author: author.to_string(),
+            group: "".to_string(),
+            extra_groups: BTreeSet::new(),
+        }
+    }
+
+    fn parse_first_entry(source: &str) -> feed_rs::model::Entry {
+        feed_rs::parser::parse(source.as_bytes())
+            .expect("fixture should parse")
+            .entries
+            .into_iter()
+            .next()
+            .expect("fixture should have one entry")
+    }
+
+    #[test]
+    fn render_item_title_does_not_rescan_substituted_text() {
+        let item = item_with(
+            "Tips for using {author} in templates",
+            "Jane",
+            "blog",
+        );
+        let rendered = render_item_title("{title} by {author}", &item);
+        assert_eq!(
+            rendered,
+            "Tips for using {author} in templates by Jane"
+        );
+    }
+
+    #[test]
+    fn render_item_title_falls_back_on_malformed_template() {
+        let item = item_with("My Title", "Jane", "blog");
+        assert_eq!(render_item_title("{title} by {unknown}", &item), "My Title");
+        assert_eq!(render_item_title("{title", &item), "My Title");
+    }
+
+    #[test]
+    fn is_valid_item_title_template_rejects_unknown_placeholders_and_unbalanced_braces() {
+        assert!(is_valid_item_title_template("[{group}] {title}"));
+        assert!(!is_valid_item_title_template("{bogus}"));
+        assert!(!is_valid_item_title_template("{title"));
+        assert!(!is_valid_item_title_template("title}"));
+        assert!(!is_valid_item_title_template("{{title}}"));
+    }
+
+    #[test]
+    fn channel_patterns_find_groups_matches_url_or_title_and_unions_groups() {
+        let patterns = ChannelPatterns::parse(
+            "https://.*\\.rs:lang-rust,.*Weekly.*:digest newsletter",
+        );
+
+        let mut expected = BTreeSet::new();
+        expected.insert("lang-rust".to_string());
+        assert_eq!(
+            patterns.find_groups("https://example.rs/feed.xml", "Example"),
+            expected
+        );
+
+        let mut expected = BTreeSet::new();
+        expected.insert("digest".to_string());
+        expected.insert("newsletter".to_string());
+        assert_eq!(
+            patterns.find_groups("https://example.com/feed.xml", "Rust Weekly News"),
+            expected
+        );
+
+        assert!(patterns
+            .find_groups("https://example.com/feed.xml", "Unrelated")
+            .is_empty());
+    }
+
+    #[test]
+    fn channel_patterns_regex_is_anchored() {
+        let patterns = ChannelPatterns::parse("rust:lang-rust");
+        assert!(patterns.find_groups("rust", "").contains("lang-rust"));
+        assert!(patterns.find_groups("rust-lang", "").is_empty());
+        assert!(patterns.find_groups("not-rust", "").is_empty());
+    }
+
+    #[test]
+    fn channel_patterns_skips_invalid_rules() {
+        let patterns = ChannelPatterns::parse("(unclosed:group, no-colon-rule, valid:group2");
+        assert!(patterns.find_groups("valid", "").contains("group2"));
+    }
+
+    #[test]
+    fn split_by_group_does_not_double_list_an_item_whose_keys_collide() {
+        let mut item = item_with("Post", "Jane", "blog");
+        item.extra_groups.insert("blog".to_string());
+
+        let groups = split_by_group(&vec![item]);
+        assert_eq!(groups.get("blog").map(|v| v.len()), Some(1));
+        assert_eq!(groups.get("").map(|v| v.len()), Some(1));
+    }
+
+    #[test]
+    fn parse_itunes_duration_normalizes_all_shapes() {
+        assert_eq!(parse_itunes_duration("01:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_itunes_duration("02:03"), Some(Duration::from_secs(123)));
+        assert_eq!(parse_itunes_duration("125"), Some(Duration::from_secs(125)));
+        assert_eq!(parse_itunes_duration(""), None);
+        assert_eq!(parse_itunes_duration("not-a-duration"), None);
+    }
+
+    #[test]
+    fn sanitize_filename_strips_illegal_characters_and_length() {
+        assert_eq!(
+            sanitize_filename("Episode 1: The Beginning!"),
+            "Episode_1__The_Beginning"
+        );
+        assert_eq!(sanitize_filename("../../etc/passwd"), ".._.._etc_passwd");
+        assert_eq!(sanitize_filename(""), "episode");
+        assert_eq!(sanitize_filename("///"), "episode");
+        assert_eq!(sanitize_filename(&"a".repeat(200)).len(), 100);
+    }
+
+    #[test]
+    fn episode_filename_ignores_query_string_and_fragment() {
+        let mut item = item_with("My Episode", "Jane", "");
+        item.enclosure = Some(Enclosure {
+            url: "https://cdn.example.com/episode123.mp3?sig=xyz.abc".to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            length: None,
+            duration: None,
+        });
+        assert_eq!(
+            episode_filename(&item),
+            Some("2024-01-01-My_Episode.mp3".to_string())
+        );
+    }
+
+    #[test]
+    fn episode_filename_falls_back_when_no_clean_extension() {
+        let mut item = item_with("My Episode", "Jane", "");
+        item.enclosure = Some(Enclosure {
+            url: "https://cdn.example.com/stream?id=42".to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            length: None,
+            duration: None,
+        });
+        assert_eq!(
+            episode_filename(&item),
+            Some("2024-01-01-My_Episode.mp3".to_string())
+        );
+    }
+
+    #[test]
+    fn episode_filename_does_not_collide_for_same_titled_episodes_on_different_dates() {
+        let mut older = item_with("Weekly Update", "Jane", "");
+        older.date = chrono::DateTime::parse_from_rfc2822("Mon, 1 Jan 2024 00:00:00 +0000").unwrap();
+        older.enclosure = Some(Enclosure {
+            url: "https://cdn.example.com/a.mp3".to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            length: None,
+            duration: None,
+        });
+
+        let mut newer = item_with("Weekly Update", "Jane", "");
+        newer.date = chrono::DateTime::parse_from_rfc2822("Mon, 8 Jan 2024 00:00:00 +0000").unwrap();
+        newer.enclosure = Some(Enclosure {
+            url: "https://cdn.example.com/b.mp3".to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            length: None,
+            duration: None,
+        });
+
+        assert_ne!(episode_filename(&older), episode_filename(&newer));
+    }
+
+    #[test]
+    fn entry_to_feeds_item_rss_date_comes_from_pub_date() {
+        let rss = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+<channel>
+<title>Test Channel</title>
+<link>https://example.com</link>
+<description>Test</description>
+<item>
+<title>Item One</title>
+<link>https://example.com/1</link>
+<pubDate>Mon, 01 Jan 2024 00:00:00 GMT</pubDate>
+</item>
+</channel>
+</rss>"#;
+        let entry = parse_first_entry(rss);
+        let channel = test_channel("Channel Author");
+        let item = entry_to_feeds_item(entry, &channel).expect("entry should convert");
+        assert_eq!(item.date.format("%Y-%m-%d").to_string(), "2024-01-01");
+        assert_eq!(item.title, "Item One");
+        assert_eq!(item.url, "https://example.com/1");
+    }
+
+    #[test]
+    fn entry_to_feeds_item_atom_falls_back_to_updated_when_no_published() {
+        let atom = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Test Feed</title>
+<id>urn:uuid:test-feed</id>
+<updated>2024-01-01T00:00:00Z</updated>
+<entry>
+<title>No Published Entry</title>
+<id>urn:uuid:entry-1</id>
+<link href="https://example.com/1"/>
+<updated>2024-02-02T00:00:00Z</updated>
+</entry>
+</feed>"#;
+        let entry = parse_first_entry(atom);
+        let channel = test_channel("Channel Author");
+        let item = entry_to_feeds_item(entry, &channel).expect("entry should convert");
+        assert_eq!(item.date.format("%Y-%m-%d").to_string(), "2024-02-02");
+    }
+
+    #[test]
+    fn entry_to_feeds_item_atom_prefers_published_over_updated() {
+        let atom = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Test Feed</title>
+<id>urn:uuid:test-feed</id>
+<updated>2024-01-01T00:00:00Z</updated>
+<entry>
+<title>Both Dates Entry</title>
+<id>urn:uuid:entry-2</id>
+<link href="https://example.com/2"/>
+<published>2024-03-03T00:00:00Z</published>
+<updated>2024-04-04T00:00:00Z</updated>
+</entry>
+</feed>"#;
+        let entry = parse_first_entry(atom);
+        let channel = test_channel("Channel Author");
+        let item = entry_to_feeds_item(entry, &channel).expect("entry should convert");
+        assert_eq!(item.date.format("%Y-%m-%d").to_string(), "2024-03-03");
+    }
+
+    #[test]
+    fn entry_to_feeds_item_author_falls_back_to_channel() {
+        let atom = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Test Feed</title>
+<id>urn:uuid:test-feed</id>
+<updated>2024-01-01T00:00:00Z</updated>
+<entry>
+<title>No Author Entry</title>
+<id>urn:uuid:entry-3</id>
+<link href="https://example.com/3"/>
+<updated>2024-01-02T00:00:00Z</updated>
+</entry>
+</feed>"#;
+        let entry = parse_first_entry(atom);
+        let channel = test_channel("Channel Author");
+        let item = entry_to_feeds_item(entry, &channel).expect("entry should convert");
+        assert_eq!(item.author, "Channel Author");
+    }
+
+    #[test]
+    fn entry_to_feeds_item_uses_entrys_own_author_when_present() {
+        let atom = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Test Feed</title>
+<id>urn:uuid:test-feed</id>
+<updated>2024-01-01T00:00:00Z</updated>
+<entry>
+<title>Has Author Entry</title>
+<id>urn:uuid:entry-4</id>
+<link href="https://example.com/4"/>
+<updated>2024-01-03T00:00:00Z</updated>
+<author><name>Jane</name></author>
+</entry>
+</feed>"#;
+        let entry = parse_first_entry(atom);
+        let channel = test_channel("Channel Author");
+        let item = entry_to_feeds_item(entry, &channel).expect("entry should convert");
+        assert_eq!(item.author, "Jane");
+    }
+
+    #[test]
+    fn entry_to_feeds_item_returns_none_when_entry_has_no_link() {
+        let atom = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+<title>Test Feed</title>
+<id>urn:uuid:test-feed</id>
+<updated>2024-01-01T00:00:00Z</updated>
+<entry>
+<title>No Link Entry</title>
+<id>urn:uuid:entry-5</id>
+<updated>2024-01-04T00:00:00Z</updated>
+</entry>
+</feed>"#;
+        let entry = parse_first_entry(atom);
+        let channel = test_channel("Channel Author");
+        assert!(entry_to_feeds_item(entry, &channel).is_none());
+    }
+
+    #[test]
+    fn entry_to_feeds_item_json_feed_round_trip() {
+        let json = r#"{
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": "Test JSON Feed",
+            "items": [
+                {
+                    "id": "1",
+                    "url": "https://example.com/1",
+                    "title": "Json Item",
+                    "date_published": "2024-01-05T00:00:00Z"
+                }
+            ]
+        }"#;
+        let entry = parse_first_entry(json);
+        let channel = test_channel("Channel Author");
+        let item = entry_to_feeds_item(entry, &channel).expect("entry should convert");
+        assert_eq!(item.title, "Json Item");
+        assert_eq!(item.url, "https://example.com/1");
+        assert_eq!(item.date.format("%Y-%m-%d").to_string(), "2024-01-05");
+    }
+
+    #[test]
+    fn extension_date_reads_namespaced_raw_date() {
+        let mut entry = feed_rs::model::Entry::default();
+        let mut extension = feed_rs::model::Extension::default();
+        extension.name = "pubDate".to_string();
+        extension.value = Some("Mon, 01 Jan 2024 00:00:00 GMT".to_string());
+        let mut ns_group = HashMap::new();
+        ns_group.insert("pubDate".to_string(), vec![extension]);
+        entry.extensions.insert("rss".to_string(), ns_group);
+
+        assert_eq!(
+            extension_date(&entry),
+            Some("Mon, 01 Jan 2024 00:00:00 GMT".to_string())
+        );
+    }
+
+    fn item_with_id(id: &str) -> FeedsItem {
+        let mut item = item_with("Post", "Jane", "");
+        item.id = id.to_string();
+        item
+    }
+
+    #[test]
+    fn diff_new_items_keeps_only_ids_absent_from_seen_set() {
+        let feeds = vec![item_with_id("a"), item_with_id("b")];
+        let mut seen = HashSet::new();
+        seen.insert("a".to_string());
+
+        let new_items = diff_new_items(&feeds, &seen);
+        assert_eq!(new_items.len(), 1);
+        assert_eq!(new_items[0].id, "b");
+    }
+
+    #[test]
+    fn current_ids_evicts_ids_no_longer_present_in_any_fetched_feed() {
+        let feeds = vec![item_with_id("a"), item_with_id("b")];
+        let ids = current_ids(&feeds);
+
+        let mut expected = HashSet::new();
+        expected.insert("a".to_string());
+        expected.insert("b".to_string());
+        assert_eq!(ids, expected);
+
+        // An id from a prior run that's no longer in this run's feeds
+        // (e.g. the item scrolled out of the source feed) is absent from
+        // `current_ids`, so it won't be carried forward into the cache.
+        assert!(!ids.contains("stale-from-a-previous-run"));
+    }
+
+    #[test]
+    fn seen_ids_round_trip_through_an_atomic_save_and_load() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("seen.json");
+
+        let mut ids = HashSet::new();
+        ids.insert("a".to_string());
+        ids.insert("b".to_string());
+
+        save_seen_ids_atomic(&path, &ids).expect("save should succeed");
+        let loaded = load_seen_ids(&path);
+        assert_eq!(loaded, ids);
+
+        // No leftover temp file after a successful save.
+        assert!(!path.with_file_name("seen.json.tmp").exists());
+    }
+
+    #[test]
+    fn load_seen_ids_returns_empty_set_when_cache_file_is_missing() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("does-not-exist.json");
+        assert!(load_seen_ids(&path).is_empty());
+    }
+
+    #[test]
+    fn write_atomic_writes_the_full_contents_and_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("out.md");
+        write_atomic(&path, b"hello world").expect("write should succeed");
+        assert_eq!(std::fs::read(&path).expect("read back"), b"hello world");
+        assert_eq!(
+            std::fs::read_dir(dir.path()).expect("read dir").count(),
+            1,
+            "no stray temp file should remain alongside the final file"
+        );
+    }
+
+    #[test]
+    fn write_atomic_overwrites_a_pre_existing_file_on_success() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("out.md");
+        std::fs::write(&path, b"old content").expect("seed original file");
+        write_atomic(&path, b"new content").expect("write should succeed");
+        assert_eq!(std::fs::read(&path).expect("read back"), b"new content");
+    }
+
+    #[test]
+    fn write_atomic_leaves_an_existing_path_untouched_when_the_rename_fails() {
+        // Point `path` at a directory instead of a file, so the temp file
+        // write succeeds but the final persist/rename step fails, and
+        // confirm the original directory (standing in for a pre-existing
+        // output file) survives exactly as it was.
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("out.md");
+        std::fs::create_dir(&path).expect("seed a directory at the target path");
+
+        let result = write_atomic(&path, b"new content");
+
+        assert!(result.is_err());
+        assert!(path.is_dir(), "pre-existing path must survive a failed write");
     }
 }